@@ -1,9 +1,28 @@
 use crate::mysql::command::{Command};
+use crate::mysql::error::ProtocolError;
+use crate::mysql::handshake::{
+    is_auth_switch_request, is_public_key_request, AuthSwitchRequest, CachingSha2Marker,
+    HandshakeResponse41, HandshakeV10,
+};
+use crate::mysql::packet::{Packet, PacketType};
+use crate::mysql::resultset::ResultSetBuilder;
 
-pub struct Connection {  
+pub struct Connection {
     pub state: State,
     pub partial_data: Option<Vec<u8>>,
-    pub last_command: Option<Box<Command>>
+    pub last_command: Option<Box<Command>>,
+    /// Set while a `COM_QUERY` response's column/row packets are being
+    /// reassembled; taken back out once `ResultSetBuilder::feed` completes it.
+    pub result_set: Option<ResultSetBuilder>,
+    handshake: Option<HandshakeV10>,
+    handshake_response: Option<HandshakeResponse41>,
+    expected_seq: u8,
+    /// How many of the in-flight packet's frames (from the front of
+    /// `partial_data`) have already had their sequence id validated by a
+    /// prior `feed()` call. `feed()` re-walks `partial_data` from byte 0 on
+    /// every call, so this is what keeps a retry after `Incomplete` from
+    /// re-validating (and re-advancing past) frames it already checked.
+    validated_frame_count: usize,
 }
 
 impl Connection {
@@ -11,7 +30,12 @@ impl Connection {
         Connection{
             state: State::Initiated,
             partial_data: None,
-            last_command: None
+            last_command: None,
+            result_set: None,
+            handshake: None,
+            handshake_response: None,
+            expected_seq: 0,
+            validated_frame_count: 0,
         }
     }
 
@@ -23,6 +47,80 @@ impl Connection {
         self.state = State::AuthDone
     }
 
+    pub fn get_handshake(&self) -> Option<&HandshakeV10> {
+        self.handshake.as_ref()
+    }
+
+    pub fn get_handshake_response(&self) -> Option<&HandshakeResponse41> {
+        self.handshake_response.as_ref()
+    }
+
+    /// Records the server's `HandshakeV10` and moves into
+    /// `State::AwaitingHandshakeResponse`, since the client's
+    /// `HandshakeResponse41` is the only valid next packet.
+    pub fn set_handshake(&mut self, packet: &Packet) -> Result<(), ProtocolError> {
+        self.handshake = Some(HandshakeV10::from_packet(packet)?);
+        self.state = State::AwaitingHandshakeResponse;
+        Ok(())
+    }
+
+    /// Records the client's `HandshakeResponse41` and moves into
+    /// `State::AwaitingAuthResult`, awaiting either an OK/Error or a plugin
+    /// challenge (`AuthSwitchRequest` / `caching_sha2_password` marker).
+    pub fn set_handshake_response(&mut self, packet: &Packet) -> Result<(), ProtocolError> {
+        self.handshake_response = Some(HandshakeResponse41::from_packet(packet)?);
+        self.state = State::AwaitingAuthResult;
+        Ok(())
+    }
+
+    /// Advances the auth state machine off a packet sent by the server during
+    /// authentication. Returns `false` if `packet` isn't an auth-flow packet at
+    /// all (the caller should then treat it as a normal Error/result); an OK
+    /// packet always completes authentication and returns `true`.
+    pub fn on_server_auth_packet(&mut self, packet: &Packet) -> Result<bool, ProtocolError> {
+        if packet.p_type == PacketType::Ok {
+            self.mark_auth_done();
+            return Ok(true);
+        }
+
+        if let Some(marker) = CachingSha2Marker::from_packet(packet) {
+            self.state = match marker {
+                CachingSha2Marker::FastAuthSuccess => State::AwaitingAuthResult,
+                CachingSha2Marker::FullAuthRequired => State::AwaitingFullAuthResult,
+            };
+            return Ok(true);
+        }
+
+        if is_auth_switch_request(packet) {
+            let switch = AuthSwitchRequest::from_packet(packet)?;
+            self.state = State::AuthSwitching {
+                plugin: switch.plugin_name,
+            };
+            return Ok(true);
+        }
+
+        // The server's reply to the client's public-key request has no fixed
+        // shape of its own (it's just the PEM-encoded key), so it can only be
+        // recognized by the state it arrives in: the client encrypts its
+        // password with it next and waits on the final OK/Error, same as any
+        // other full-auth completion.
+        if matches!(self.state, State::AwaitingPublicKey) {
+            self.state = State::AwaitingFullAuthResult;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    /// Advances the auth state machine off a packet sent by the client during
+    /// `caching_sha2_password` full authentication: a bare `0x02` requests the
+    /// server's RSA public key before the client sends its encrypted password.
+    pub fn on_client_auth_packet(&mut self, packet: &Packet) {
+        if matches!(self.state, State::AwaitingFullAuthResult) && is_public_key_request(packet) {
+            self.state = State::AwaitingPublicKey;
+        }
+    }
+
     pub fn unset_partial_data(&mut self) {
         self.partial_data = None;
     }
@@ -32,17 +130,165 @@ impl Connection {
         temp.extend_from_slice(bytes);
         self.partial_data = Some(temp);
     }
-}
 
+    /// Feeds freshly-read bytes in, prepending whatever was left over from the
+    /// previous read, and tries to reassemble one logical packet off the front.
+    ///
+    /// On success, any bytes past the reassembled packet are kept as the new
+    /// `partial_data` (there may already be the start of the next packet). On
+    /// `Incomplete`, the whole buffer is kept as `partial_data` so the next read
+    /// picks up where this one left off; this is the bridge between TCP reads,
+    /// which know nothing about frame boundaries, and `Packet::from_stream`,
+    /// which needs a full logical packet to run `get_packet_type` correctly.
+    ///
+    /// Every individual frame's sequence id is validated as `Packet::from_stream`
+    /// reads it, not just the last one attached to the reassembled `Packet` - a
+    /// split packet has one sequence id per frame on the wire, and all of them
+    /// need to line up. Since `Packet::from_stream` always walks `buffered` from
+    /// byte 0, `validated_frame_count` is used to skip re-validating frames a
+    /// prior call already checked before returning `Incomplete`.
+    pub fn feed(&mut self, bytes: &[u8], phase: Phase) -> Result<Option<Packet>, ProtocolError> {
+        let mut buffered = self.partial_data.take().unwrap_or_default();
+        buffered.extend_from_slice(bytes);
+
+        let already_validated = self.validated_frame_count;
+        let mut frame_index = 0;
+        let result = {
+            let connection: &mut Connection = self;
+            Packet::from_stream(&buffered, phase, |seq| {
+                let index = frame_index;
+                frame_index += 1;
 
+                // `from_stream` re-walks `buffered` from byte 0 on every call, so
+                // frames below `already_validated` were already checked (and the
+                // counter already advanced past them) by a prior `feed()` call
+                // that returned `Incomplete` on a later frame of this same
+                // in-flight packet - skip re-validating them here.
+                if index < already_validated {
+                    return Ok(());
+                }
 
+                let is_command_start = index == 0 && phase == Phase::Command;
+                connection.check_seq(seq, is_command_start)
+            })
+        };
+
+        match result {
+            Ok((packet, consumed)) => {
+                self.validated_frame_count = 0;
+                if consumed < buffered.len() {
+                    self.set_partial_data(&buffered[consumed..]);
+                } else {
+                    self.unset_partial_data();
+                }
+
+                Ok(Some(packet))
+            }
+            Err(ProtocolError::Incomplete { .. }) => {
+                self.validated_frame_count = frame_index;
+                self.set_partial_data(&buffered);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn reset_seq(&mut self) {
+        self.expected_seq = 0;
+    }
+
+    fn advance_seq(&mut self) {
+        self.expected_seq = self.expected_seq.wrapping_add(1);
+    }
+
+    /// Validates one wire frame's sequence id against the running counter,
+    /// advancing it on success.
+    ///
+    /// MySQL shares a single sequence counter across both directions of one
+    /// command/response round trip: the client's command is seq 0, the
+    /// server's first response frame is seq 1, and so on, with no reset in
+    /// between - only the client starting a new command resets it back to 0
+    /// (this mirrors go-sql-driver's `mc.sequence`, a single field bumped on
+    /// every read *and* write). The first frame of a new `Phase::Command`
+    /// packet therefore resets the counter before it's checked; any further
+    /// frames of a split command continue from there without a reset. A mismatch is a fatal desync (lost, duplicated or interleaved
+    /// frames) rather than something to retry, so it's reported as a distinct
+    /// `ProtocolError::SequenceMismatch` instead of folding it into the
+    /// generic parse error.
+    pub fn check_seq(&mut self, seq: u8, is_command_start: bool) -> Result<(), ProtocolError> {
+        if is_command_start {
+            self.reset_seq();
+        }
+
+        let expected = self.expected_seq;
+        if seq != expected {
+            return Err(ProtocolError::SequenceMismatch {
+                expected,
+                got: seq,
+                ahead: seq.wrapping_sub(expected) < 128,
+            });
+        }
+
+        self.advance_seq();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum State {
     Initiated,
+    /// Server's `HandshakeV10` has been seen; waiting on the client's
+    /// `HandshakeResponse41`.
+    AwaitingHandshakeResponse,
+    /// Client has responded; waiting on an OK/Error or a plugin challenge.
+    AwaitingAuthResult,
+    /// Server sent an `AuthSwitchRequest` naming `plugin`; waiting on the
+    /// client's response to the new challenge.
+    AuthSwitching { plugin: String },
+    /// `caching_sha2_password` full authentication: client asked for the
+    /// server's RSA public key and is waiting on it.
+    AwaitingPublicKey,
+    /// `caching_sha2_password` full authentication: entered on the server's
+    /// full-auth marker (and again once the public key has arrived); the
+    /// client sends its password, or first asks for the public key, and then
+    /// waits on the final OK/Error.
+    AwaitingFullAuthResult,
     AuthDone,
     PendingResponse
 }
 
-#[derive(Debug)]
-pub enum Direction {
-    C2S, S2C
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_counter_tracks_frames_across_both_directions() {
+        let mut connection = Connection::new();
+
+        // The client's command is frame seq 0.
+        connection.check_seq(0, true).unwrap();
+        // A two-frame split response from the server continues the same
+        // counter at 1 and 2, with no reset in between.
+        connection.check_seq(1, false).unwrap();
+        connection.check_seq(2, false).unwrap();
+
+        // Only the client starting a new command resets the counter back to 0.
+        connection.check_seq(0, true).unwrap();
+    }
+
+    #[test]
+    fn stale_frame_is_reported_as_sequence_mismatch() {
+        let mut connection = Connection::new();
+
+        connection.check_seq(0, true).unwrap();
+        let err = connection.check_seq(0, false).unwrap_err();
+        assert!(matches!(
+            err,
+            ProtocolError::SequenceMismatch {
+                expected: 1,
+                got: 0,
+                ahead: false,
+            }
+        ));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,70 @@
+use std::fmt;
+
+use crate::mysql::packet::ErrorData;
+
+/// Errors from parsing the MySQL wire protocol, in the style of
+/// rust-postgres's `ConnectError`: distinct variants rather than one opaque
+/// catch-all, so callers can tell "wait for more bytes" apart from a genuine
+/// protocol violation.
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// Not enough bytes are available yet to parse the next logical packet -
+    /// fewer than 4 header bytes, or fewer than `4 + size` body bytes. Callers
+    /// should buffer `needed` more bytes into `Connection::partial_data` and
+    /// retry rather than treating this as a parse failure.
+    Incomplete { needed: usize },
+    /// The bytes present don't form a valid packet for the current phase.
+    Malformed,
+    /// A frame's sequence id didn't match the expected counter, which is
+    /// shared across both directions of one command/response round trip (see
+    /// `Connection::check_seq`). `ahead` means frames were skipped; otherwise
+    /// it's a duplicate or stale frame - mirroring go-sql-driver's
+    /// `ErrPktSyncMul` vs `ErrPktSync`.
+    SequenceMismatch { expected: u8, got: u8, ahead: bool },
+    /// The packet was well-formed but this client has no decoder for it.
+    UnsupportedPacket,
+    /// The server reported an Error packet somewhere a successful response
+    /// was expected (e.g. partway through a result set's row block).
+    ServerError(ErrorData),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Incomplete { needed } => {
+                write!(f, "incomplete packet, need {} more byte(s)", needed)
+            }
+            ProtocolError::Malformed => write!(f, "malformed packet"),
+            ProtocolError::SequenceMismatch { expected, got, ahead } => write!(
+                f,
+                "sequence id mismatch: expected {}, got {} ({})",
+                expected,
+                got,
+                if *ahead { "ahead" } else { "behind" }
+            ),
+            ProtocolError::UnsupportedPacket => write!(f, "unsupported packet"),
+            ProtocolError::ServerError(error) => write!(
+                f,
+                "server error {}: {}",
+                error.error_code, error.error_message
+            ),
+            ProtocolError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProtocolError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ProtocolError {
+    fn from(err: std::io::Error) -> Self {
+        ProtocolError::Io(err)
+    }
+}
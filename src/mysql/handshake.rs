@@ -0,0 +1,336 @@
+use crate::mysql::accumulator::CapabilityFlags;
+use crate::mysql::error::ProtocolError;
+use crate::mysql::packet::{Packet, PacketType};
+use crate::mysql::types::{Converter, IntFixedLen, StringFixedLen, StringLenEnc, StringNulEnc};
+
+/// The server's initial `HandshakeV10` packet.
+#[derive(Debug, Clone)]
+pub struct HandshakeV10 {
+    pub protocol_version: u8,
+    pub server_version: String,
+    pub connection_id: u32,
+    pub auth_plugin_data: Vec<u8>,
+    pub capability_flags: u32,
+    pub charset: u8,
+    pub status_flags: u16,
+    pub auth_plugin_name: Option<String>,
+}
+
+impl HandshakeV10 {
+    pub fn from_packet(packet: &Packet) -> Result<HandshakeV10, ProtocolError> {
+        let body = &packet.body;
+        let mut offset = 0;
+
+        let protocol_version = *body.get(offset).ok_or(ProtocolError::Malformed)?;
+        offset += 1;
+
+        let server_version = {
+            let result = StringNulEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+
+        let connection_id = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(4));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+
+        let mut auth_plugin_data = {
+            let result = StringFixedLen::from_bytes(&body[offset..].to_vec(), Some(8));
+            offset += result.offset_increment;
+            result.result.into_bytes()
+        };
+
+        offset += 1; // filler, always 0x00
+
+        let capability_flags_lower = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+
+        let charset = *body.get(offset).ok_or(ProtocolError::Malformed)?;
+        offset += 1;
+
+        let status_flags = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u16
+        };
+
+        let capability_flags_upper = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+        let capability_flags = capability_flags_lower | (capability_flags_upper << 16);
+
+        let auth_plugin_data_len = *body.get(offset).ok_or(ProtocolError::Malformed)?;
+        offset += 1;
+
+        offset += 10; // reserved
+
+        // part 2 is at least 13 bytes (12 bytes of data plus the trailing NUL),
+        // regardless of what auth_plugin_data_len claims. auth_plugin_data_len
+        // is legitimately 0x00 when CLIENT_PLUGIN_AUTH isn't negotiated, so the
+        // subtraction has to saturate instead of underflowing.
+        let part2_len = std::cmp::max(13, (auth_plugin_data_len as usize).saturating_sub(8));
+        if offset + part2_len > body.len() || part2_len == 0 {
+            return Err(ProtocolError::Malformed);
+        }
+        auth_plugin_data.extend_from_slice(&body[offset..offset + part2_len - 1]);
+        offset += part2_len;
+
+        let auth_plugin_name = {
+            let result = StringNulEnc::from_bytes(&body[offset..].to_vec(), None);
+            Some(result.result)
+        };
+
+        Ok(HandshakeV10 {
+            protocol_version,
+            server_version,
+            connection_id,
+            auth_plugin_data,
+            capability_flags,
+            charset,
+            status_flags,
+            auth_plugin_name,
+        })
+    }
+}
+
+/// The client's `HandshakeResponse41`, sent in reply to `HandshakeV10`.
+#[derive(Debug, Clone)]
+pub struct HandshakeResponse41 {
+    pub client_flag: u32,
+    pub max_packet_size: u32,
+    pub character_set: u8,
+    pub username: String,
+    pub auth_response: Vec<u8>,
+    pub database: Option<String>,
+    pub auth_plugin_name: Option<String>,
+}
+
+impl HandshakeResponse41 {
+    pub fn from_packet(packet: &Packet) -> Result<HandshakeResponse41, ProtocolError> {
+        let body = &packet.body;
+        let mut offset = 0;
+
+        let client_flag = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(4));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+
+        let max_packet_size = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(4));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+
+        let character_set = *body.get(offset).ok_or(ProtocolError::Malformed)?;
+        offset += 1;
+
+        offset += 23; // reserved filler
+        if offset > body.len() {
+            return Err(ProtocolError::Malformed);
+        }
+
+        let username = {
+            let result = StringNulEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+
+        let auth_response = if client_flag & CapabilityFlags::ClientPluginAuthLenencClientData as u32 != 0 {
+            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result.into_bytes()
+        } else {
+            let len = *body.get(offset).ok_or(ProtocolError::Malformed)? as usize;
+            offset += 1;
+            if offset + len > body.len() {
+                return Err(ProtocolError::Malformed);
+            }
+            let data = body[offset..offset + len].to_vec();
+            offset += len;
+            data
+        };
+
+        let database = if client_flag & CapabilityFlags::ClientConnectWithDb as u32 != 0 {
+            let result = StringNulEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            Some(result.result)
+        } else {
+            None
+        };
+
+        let auth_plugin_name = if client_flag & CapabilityFlags::ClientPluginAuth as u32 != 0 {
+            let result = StringNulEnc::from_bytes(&body[offset..].to_vec(), None);
+            Some(result.result)
+        } else {
+            None
+        };
+
+        Ok(HandshakeResponse41 {
+            client_flag,
+            max_packet_size,
+            character_set,
+            username,
+            auth_response,
+            database,
+            auth_plugin_name,
+        })
+    }
+}
+
+/// `0xFE` sent mid-authentication instead of OK/Error: the server wants the
+/// client to switch to a different auth plugin and restart the challenge with
+/// `plugin_data` as the new nonce/salt.
+#[derive(Debug, Clone)]
+pub struct AuthSwitchRequest {
+    pub plugin_name: String,
+    pub plugin_data: Vec<u8>,
+}
+
+impl AuthSwitchRequest {
+    pub fn from_packet(packet: &Packet) -> Result<AuthSwitchRequest, ProtocolError> {
+        let body = &packet.body;
+        if body.first() != Some(&0xfe) {
+            return Err(ProtocolError::Malformed);
+        }
+
+        let mut offset = 1;
+        let plugin_name = {
+            let result = StringNulEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+        let plugin_data = body[offset..].to_vec();
+
+        Ok(AuthSwitchRequest {
+            plugin_name,
+            plugin_data,
+        })
+    }
+}
+
+/// The two one-packet markers `caching_sha2_password` sends instead of a
+/// normal `AuthSwitchRequest`: `0x01 0x03` means the fast-auth hash matched
+/// and an OK packet follows, `0x01 0x04` means the server has no cached hash
+/// and the client must perform full authentication (send the plaintext
+/// password over TLS, or request the server's RSA public key first).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CachingSha2Marker {
+    FastAuthSuccess,
+    FullAuthRequired,
+}
+
+impl CachingSha2Marker {
+    pub fn from_packet(packet: &Packet) -> Option<CachingSha2Marker> {
+        match packet.body.as_slice() {
+            [0x01, 0x03] => Some(CachingSha2Marker::FastAuthSuccess),
+            [0x01, 0x04] => Some(CachingSha2Marker::FullAuthRequired),
+            _ => None,
+        }
+    }
+}
+
+/// The client's request, during `caching_sha2_password` full authentication,
+/// for the server's RSA public key: a single `0x02` byte.
+pub fn is_public_key_request(packet: &Packet) -> bool {
+    packet.body.as_slice() == [0x02]
+}
+
+/// Whether `packet` is a mid-handshake `AuthSwitchRequest`, as opposed to a
+/// `caching_sha2_password` marker, an OK, or an Error.
+pub fn is_auth_switch_request(packet: &Packet) -> bool {
+    packet.p_type != PacketType::Ok
+        && packet.p_type != PacketType::Error
+        && packet.body.first() == Some(&0xfe)
+        && packet.body.len() > 1
+        && CachingSha2Marker::from_packet(packet).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mysql::packet::PacketHeader;
+
+    /// Builds a `HandshakeV10` body with `auth_plugin_data_len` set as given,
+    /// zero-filling the auth-plugin-data bytes it doesn't actually carry.
+    fn handshake_v10_body(auth_plugin_data_len: u8) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.push(10); // protocol_version
+        body.extend_from_slice(b"8.0.0\0"); // server_version
+        body.extend_from_slice(&42u32.to_le_bytes()); // connection_id
+        body.extend_from_slice(&[0u8; 8]); // auth_plugin_data part 1
+        body.push(0); // filler
+        body.extend_from_slice(&0u16.to_le_bytes()); // capability_flags_lower
+        body.push(0); // charset
+        body.extend_from_slice(&0u16.to_le_bytes()); // status_flags
+        body.extend_from_slice(&0u16.to_le_bytes()); // capability_flags_upper
+        body.push(auth_plugin_data_len);
+        body.extend_from_slice(&[0u8; 10]); // reserved
+        body.extend_from_slice(&[0u8; 12]); // auth_plugin_data part 2
+        body.push(0); // part 2 trailing NUL
+        body.extend_from_slice(b"mysql_native_password\0"); // auth_plugin_name
+        body
+    }
+
+    fn packet_with_body(body: Vec<u8>) -> Packet {
+        Packet {
+            header: PacketHeader { size: body.len(), seq: 0 },
+            body,
+            p_type: PacketType::Other,
+        }
+    }
+
+    #[test]
+    fn handshake_v10_does_not_panic_when_plugin_auth_is_not_negotiated() {
+        // auth_plugin_data_len is legitimately 0x00 when CLIENT_PLUGIN_AUTH
+        // isn't negotiated; this used to underflow `auth_plugin_data_len - 8`.
+        let packet = packet_with_body(handshake_v10_body(0));
+        let handshake = HandshakeV10::from_packet(&packet).unwrap();
+        assert_eq!(handshake.auth_plugin_data.len(), 8 + 12);
+    }
+
+    #[test]
+    fn handshake_v10_parses_a_typical_auth_plugin_data_len() {
+        let packet = packet_with_body(handshake_v10_body(21));
+        let handshake = HandshakeV10::from_packet(&packet).unwrap();
+        assert_eq!(handshake.auth_plugin_data.len(), 8 + 12);
+        assert_eq!(handshake.auth_plugin_name.as_deref(), Some("mysql_native_password"));
+    }
+
+    #[test]
+    fn handshake_response_41_rejects_a_body_truncated_before_auth_response_length() {
+        // client_flag lacks ClientPluginAuthLenencClientData, so auth_response
+        // is read as a 1-byte length followed by that many bytes - but the
+        // body ends right before the length byte.
+        let mut body = 0u32.to_le_bytes().to_vec(); // client_flag
+        body.extend_from_slice(&0u32.to_le_bytes()); // max_packet_size
+        body.push(0); // character_set
+        body.extend_from_slice(&[0u8; 23]); // reserved
+        body.extend_from_slice(b"root\0"); // username
+
+        let packet = packet_with_body(body);
+        let err = HandshakeResponse41::from_packet(&packet).unwrap_err();
+        assert!(matches!(err, ProtocolError::Malformed));
+    }
+
+    #[test]
+    fn handshake_response_41_rejects_an_auth_response_length_past_the_body() {
+        let mut body = 0u32.to_le_bytes().to_vec(); // client_flag
+        body.extend_from_slice(&0u32.to_le_bytes()); // max_packet_size
+        body.push(0); // character_set
+        body.extend_from_slice(&[0u8; 23]); // reserved
+        body.extend_from_slice(b"root\0"); // username
+        body.push(200); // auth_response length - far more than the 0 bytes that follow
+
+        let packet = packet_with_body(body);
+        let err = HandshakeResponse41::from_packet(&packet).unwrap_err();
+        assert!(matches!(err, ProtocolError::Malformed));
+    }
+}
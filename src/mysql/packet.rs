@@ -1,7 +1,7 @@
 use crate::connection::{Connection, Phase};
 use crate::mysql::accumulator::CapabilityFlags;
-use crate::mysql::types::{Converter, IntFixedLen, IntLenEnc, StringEOFEnc, StringFixedLen};
-use std::{fmt::Error, usize};
+use crate::mysql::error::ProtocolError;
+use crate::mysql::types::{Converter, IntFixedLen, IntLenEnc, StringEOFEnc, StringFixedLen, StringLenEnc};
 
 #[derive(Debug)]
 pub struct Packet {
@@ -45,17 +45,27 @@ impl PacketHeader {
     }
 }
 
+/// Frames of this length (0xFFFFFF, the max value a 3-byte header can hold) never
+/// stand alone: the logical packet continues in a following frame, and a payload
+/// whose size is an exact multiple of this value is terminated by a trailing
+/// zero-length frame.
+pub const MAX_FRAME_SIZE: usize = 0xFF_FFFF;
+
 impl Packet {
-    pub fn from_bytes(bytes: &[u8], phase: Phase) -> Result<Packet, Error> {
+    pub fn from_bytes(bytes: &[u8], phase: Phase) -> Result<Packet, ProtocolError> {
         if bytes.len() < 4 {
-            return Err(Error {});
+            return Err(ProtocolError::Incomplete {
+                needed: 4 - bytes.len(),
+            });
         }
 
         let raw_header: [u8; 4] = bytes[0..4].try_into().expect("Slice with incorrect length");
         let header = PacketHeader::from_bytes(&raw_header);
 
         if bytes.len() < 4 + header.size {
-            return Err(Error {});
+            return Err(ProtocolError::Incomplete {
+                needed: (4 + header.size) - bytes.len(),
+            });
         }
         let body = bytes[4..4 + header.size].to_vec();
 
@@ -68,6 +78,75 @@ impl Packet {
         })
     }
 
+    /// Reassembles a logical packet from one or more consecutive frames at the
+    /// front of `bytes`, returning the packet along with how many bytes of `bytes`
+    /// it consumed.
+    ///
+    /// MySQL splits any payload whose length reaches `MAX_FRAME_SIZE` across
+    /// several frames on the wire, each with its own 4-byte header and an
+    /// incrementing sequence id. This keeps reading frames and concatenating their
+    /// bodies until it sees a frame shorter than `MAX_FRAME_SIZE` - including a
+    /// trailing empty frame, which is how a split packet whose size is an exact
+    /// multiple of `MAX_FRAME_SIZE` is terminated. `get_packet_type` only runs once
+    /// the full body has been assembled, so a split result row is never mis-typed
+    /// from its first frame alone.
+    ///
+    /// `on_frame_seq` is invoked with every individual frame's sequence id as it's
+    /// read off the wire, before its body is appended - each frame has its own
+    /// sequence id, so a caller validating sequencing needs to see all of them,
+    /// not just the last one attached to the reassembled `Packet`.
+    pub fn from_stream<F>(bytes: &[u8], phase: Phase, mut on_frame_seq: F) -> Result<(Packet, usize), ProtocolError>
+    where
+        F: FnMut(u8) -> Result<(), ProtocolError>,
+    {
+        let mut body: Vec<u8> = Vec::new();
+        let mut offset = 0;
+        let mut last_seq: u8 = 0;
+
+        loop {
+            if bytes.len() < offset + 4 {
+                return Err(ProtocolError::Incomplete {
+                    needed: (offset + 4) - bytes.len(),
+                });
+            }
+
+            let raw_header: [u8; 4] = bytes[offset..offset + 4]
+                .try_into()
+                .expect("Slice with incorrect length");
+            let header = PacketHeader::from_bytes(&raw_header);
+
+            if bytes.len() < offset + 4 + header.size {
+                return Err(ProtocolError::Incomplete {
+                    needed: (offset + 4 + header.size) - bytes.len(),
+                });
+            }
+
+            on_frame_seq(header.seq)?;
+
+            body.extend_from_slice(&bytes[offset + 4..offset + 4 + header.size]);
+            last_seq = header.seq;
+            offset += 4 + header.size;
+
+            if header.size < MAX_FRAME_SIZE {
+                break;
+            }
+        }
+
+        let p_type: PacketType = get_packet_type(&body, phase);
+
+        Ok((
+            Packet {
+                header: PacketHeader {
+                    size: body.len(),
+                    seq: last_seq,
+                },
+                body,
+                p_type,
+            },
+            offset,
+        ))
+    }
+
     #[allow(dead_code)]
     pub fn to_bytes(self) -> Vec<u8> {
         let mut ret: Vec<u8> = Vec::new();
@@ -113,55 +192,69 @@ pub struct ErrorData {
 }
 
 impl ErrorData {
-    pub fn from_packet(packet: &Packet, connection: &Connection) -> ErrorData {
-        assert_eq!(packet.p_type, PacketType::Error);
+    pub fn from_packet(packet: &Packet, connection: &Connection) -> Result<ErrorData, ProtocolError> {
+        if packet.p_type != PacketType::Error {
+            return Err(ProtocolError::Malformed);
+        }
         let body = &packet.body;
 
         let mut offset = 1;
 
-        ErrorData {
-            error_code: {
-                let result = IntFixedLen::from_bytes(body, Some(2));
-                offset += result.offset_increment;
-                result.result as u16
-            },
-            sql_state: {
-                let sql_state = get_sql_state(packet, connection, &offset);
-                offset += 6;
-                sql_state
-            },
-            error_message: {
-                let result = StringEOFEnc::from_bytes(&body[offset..].to_vec(), None);
-                offset += result.offset_increment;
-                assert_eq!(offset, body.len());
-                result.result
-            },
-        }
+        let error_code = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u16
+        };
+        let sql_state = {
+            let sql_state = get_sql_state(packet, connection, &offset)?;
+            offset += 6;
+            sql_state
+        };
+        let error_message = {
+            let result = StringEOFEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            if offset != body.len() {
+                return Err(ProtocolError::Malformed);
+            }
+            result.result
+        };
+
+        Ok(ErrorData {
+            error_code,
+            sql_state,
+            error_message,
+        })
     }
 }
 
-fn get_sql_state(packet: &Packet, connection: &Connection, offset: &usize) -> Option<SQLState> {
+fn get_sql_state(
+    packet: &Packet,
+    connection: &Connection,
+    offset: &usize,
+) -> Result<Option<SQLState>, ProtocolError> {
     if connection.get_handshake_response().unwrap().client_flag
         & CapabilityFlags::ClientProtocol41 as u32
         == 0
     {
-        return None;
+        return Ok(None);
     }
 
     let mut state_offset = *offset;
-    Some(SQLState {
-        state_marker: {
-            let result = StringFixedLen::from_bytes(&packet.body[state_offset..].to_vec(), Some(1));
-            state_offset += result.offset_increment;
-            result.result
-        },
-        state: {
-            let result = StringFixedLen::from_bytes(&packet.body[state_offset..].to_vec(), Some(5));
-            state_offset += result.offset_increment;
-            assert_eq!(state_offset - offset, 6);
-            result.result
-        },
-    })
+    let state_marker = {
+        let result = StringFixedLen::from_bytes(&packet.body[state_offset..].to_vec(), Some(1));
+        state_offset += result.offset_increment;
+        result.result
+    };
+    let state = {
+        let result = StringFixedLen::from_bytes(&packet.body[state_offset..].to_vec(), Some(5));
+        state_offset += result.offset_increment;
+        result.result
+    };
+    if state_offset - offset != 6 {
+        return Err(ProtocolError::Malformed);
+    }
+
+    Ok(Some(SQLState { state_marker, state }))
 }
 
 #[derive(Debug, Clone)]
@@ -170,10 +263,58 @@ pub struct SQLState {
     state: String,
 }
 
-#[derive(Debug)]
-pub struct SessionState {
-    type_: u8,
-    data: String,
+/// One `SESSION_TRACK` state-change record from an OK packet's
+/// `session_state_info` blob, decoded per its `type` byte.
+#[derive(Debug, Clone)]
+pub enum SessionState {
+    SystemVariableChanged { name: String, value: String },
+    Schema(String),
+    StateChange(bool),
+    Gtids(String),
+    TransactionCharacteristics(String),
+    TransactionState(String),
+    /// A type byte this client doesn't know how to interpret yet, kept as the
+    /// raw record data rather than dropped.
+    Other { type_: u8, data: Vec<u8> },
+}
+
+impl SessionState {
+    fn from_record(type_: u8, data: &[u8]) -> Result<SessionState, ProtocolError> {
+        Ok(match type_ {
+            0 => {
+                let mut offset = 0;
+                let name = {
+                    let result = StringLenEnc::from_bytes(&data[offset..].to_vec(), None);
+                    offset += result.offset_increment;
+                    result.result
+                };
+                let value = {
+                    let result = StringLenEnc::from_bytes(&data[offset..].to_vec(), None);
+                    offset += result.offset_increment;
+                    result.result
+                };
+                if offset != data.len() {
+                    return Err(ProtocolError::Malformed);
+                }
+                SessionState::SystemVariableChanged { name, value }
+            }
+            1 => {
+                let result = StringLenEnc::from_bytes(&data.to_vec(), None);
+                SessionState::Schema(result.result)
+            }
+            2 => {
+                let result = StringLenEnc::from_bytes(&data.to_vec(), None);
+                SessionState::StateChange(result.result == "1")
+            }
+            3 => SessionState::Gtids(String::from_utf8_lossy(data).into_owned()),
+            4 => SessionState::TransactionCharacteristics(String::from_utf8_lossy(data).into_owned()),
+            5 => SessionState::TransactionState(String::from_utf8_lossy(data).into_owned()),
+            _ => SessionState::Other {
+                type_,
+                data: data.to_vec(),
+            },
+        })
+    }
 }
 
 #[repr(u16)]
@@ -189,12 +330,14 @@ pub struct OkData {
     status_flags: Option<u16>,
     warnings: Option<u16>,
     info: Option<String>,
-    session_state_info: Option<SessionState>,
+    session_state_info: Vec<SessionState>,
 }
 
 impl OkData {
-    pub fn from_packet(packet: &Packet, connection: &Connection) -> OkData {
-        assert_eq!(packet.p_type, PacketType::Ok);
+    pub fn from_packet(packet: &Packet, connection: &Connection) -> Result<OkData, ProtocolError> {
+        if packet.p_type != PacketType::Ok {
+            return Err(ProtocolError::Malformed);
+        }
 
         let mut offset = 1;
         let body = &packet.body;
@@ -239,28 +382,224 @@ impl OkData {
         }
 
         let mut info = None;
+        let mut session_state_info: Vec<SessionState> = Vec::new();
         if connection.get_handshake_response().unwrap().client_flag
             & CapabilityFlags::ClientSessionTrack as u32
             != 0
         {
-            // The documentation is not clear about the condition below, how do we infer if status is not empty??
-            // https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_basic_ok_packet.html
-            // if (status_flags.unwrap() & ServerStatusFlags::ServerSessionStateChanged as u16) != 0 {
-            //     info = {
-            //         let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
-            //         offset += result.offset_increment;
-            //         Some(result.result)
-            //     }
-            // }
+            if status_flags.unwrap_or(0) & ServerStatusFlags::ServerSessionStateChanged as u16 != 0 {
+                info = Some({
+                    let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+                    offset += result.offset_increment;
+                    result.result
+                });
+
+                let session_state_changes_len = {
+                    let result = IntLenEnc::from_bytes(&body[offset..].to_vec(), None);
+                    offset += result.offset_increment;
+                    result.result as usize
+                };
+                let session_state_changes_end = offset + session_state_changes_len;
+
+                while offset < session_state_changes_end {
+                    let record_type = *body.get(offset).ok_or(ProtocolError::Malformed)?;
+                    offset += 1;
+
+                    let record_len = {
+                        let result = IntLenEnc::from_bytes(&body[offset..].to_vec(), None);
+                        offset += result.offset_increment;
+                        result.result as usize
+                    };
+                    if offset + record_len > body.len() {
+                        return Err(ProtocolError::Malformed);
+                    }
+                    let record_data = &body[offset..offset + record_len];
+                    offset += record_len;
+
+                    session_state_info.push(SessionState::from_record(record_type, record_data)?);
+                }
+                if offset != session_state_changes_end {
+                    return Err(ProtocolError::Malformed);
+                }
+            } else {
+                info = Some({
+                    let result = StringEOFEnc::from_bytes(&body[offset..].to_vec(), None);
+                    offset += result.offset_increment;
+                    result.result
+                });
+            }
         }
 
-        OkData {
+        Ok(OkData {
             affected_rows,
             last_insert_id,
             status_flags,
             warnings,
             info,
-            session_state_info: None,
-        }
+            session_state_info,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(seq: u8, body: &[u8]) -> Vec<u8> {
+        let header = PacketHeader { size: body.len(), seq };
+        let mut bytes = header.to_bytes().to_vec();
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    fn lenenc_str(s: &str) -> Vec<u8> {
+        assert!(s.len() < 0xfb, "test helper only covers single-byte lenenc ints");
+        let mut bytes = vec![s.len() as u8];
+        bytes.extend_from_slice(s.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn reassembles_a_packet_split_across_a_max_size_frame() {
+        // A payload that needs splitting across more than one frame: the
+        // first frame is exactly MAX_FRAME_SIZE bytes (the largest a single
+        // frame can carry), so a second, shorter frame carrying the rest is
+        // required to terminate the logical packet.
+        let first_frame_body = vec![0x41u8; MAX_FRAME_SIZE];
+        let second_frame_body = vec![0x42u8; 5];
+
+        let mut bytes = frame(0, &first_frame_body);
+        bytes.extend(frame(1, &second_frame_body));
+
+        let mut seen_seqs = Vec::new();
+        let (packet, consumed) = Packet::from_stream(&bytes, Phase::Command, |seq| {
+            seen_seqs.push(seq);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(seen_seqs, vec![0, 1]);
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(packet.body.len(), MAX_FRAME_SIZE + 5);
+        assert_eq!(packet.header.seq, 1);
+    }
+
+    #[test]
+    fn from_stream_reports_incomplete_without_validating_the_partial_frame() {
+        // The second frame's header says 5 bytes are coming, but only 2 have
+        // arrived so far - from_stream must report Incomplete before calling
+        // on_frame_seq for this still-partial frame, which is what lets a
+        // caller like Connection::feed track how many frames it already
+        // validated across retries.
+        let first_frame_body = vec![0x41u8; MAX_FRAME_SIZE];
+        let mut bytes = frame(0, &first_frame_body);
+        bytes.extend(frame(1, &[0x42, 0x42, 0x42, 0x42, 0x42])[..4 + 2].to_vec());
+
+        let mut seen_seqs = Vec::new();
+        let err = Packet::from_stream(&bytes, Phase::Command, |seq| {
+            seen_seqs.push(seq);
+            Ok(())
+        })
+        .unwrap_err();
+
+        assert!(matches!(err, ProtocolError::Incomplete { .. }));
+        assert_eq!(seen_seqs, vec![0]);
+    }
+
+    #[test]
+    fn session_state_parses_system_variable_changed() {
+        let mut data = lenenc_str("autocommit");
+        data.extend(lenenc_str("ON"));
+        let state = SessionState::from_record(0, &data).unwrap();
+        assert!(matches!(
+            state,
+            SessionState::SystemVariableChanged { name, value }
+                if name == "autocommit" && value == "ON"
+        ));
+    }
+
+    #[test]
+    fn session_state_parses_schema_and_state_change() {
+        let schema = SessionState::from_record(1, &lenenc_str("new_schema")).unwrap();
+        assert!(matches!(schema, SessionState::Schema(s) if s == "new_schema"));
+
+        let state_change = SessionState::from_record(2, &lenenc_str("1")).unwrap();
+        assert!(matches!(state_change, SessionState::StateChange(true)));
+    }
+
+    #[test]
+    fn session_state_parses_gtids_and_transaction_records() {
+        assert!(matches!(
+            SessionState::from_record(3, b"uuid:1-5").unwrap(),
+            SessionState::Gtids(s) if s == "uuid:1-5"
+        ));
+        assert!(matches!(
+            SessionState::from_record(4, b"chars").unwrap(),
+            SessionState::TransactionCharacteristics(s) if s == "chars"
+        ));
+        assert!(matches!(
+            SessionState::from_record(5, b"state").unwrap(),
+            SessionState::TransactionState(s) if s == "state"
+        ));
+    }
+
+    #[test]
+    fn session_state_keeps_unknown_record_types_as_raw_data() {
+        let state = SessionState::from_record(42, &[1, 2, 3]).unwrap();
+        assert!(matches!(state, SessionState::Other { type_: 42, data } if data == vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn session_state_rejects_system_variable_changed_with_trailing_bytes() {
+        let mut data = lenenc_str("autocommit");
+        data.extend(lenenc_str("ON"));
+        data.push(0xff); // trailing garbage past the value
+        let err = SessionState::from_record(0, &data).unwrap_err();
+        assert!(matches!(err, ProtocolError::Malformed));
+    }
+
+    fn connection_with_session_track() -> Connection {
+        let mut connection = Connection::new();
+        let client_flag = CapabilityFlags::ClientProtocol41 as u32
+            | CapabilityFlags::ClientTransactions as u32
+            | CapabilityFlags::ClientSessionTrack as u32;
+        let mut body = client_flag.to_le_bytes().to_vec();
+        body.extend_from_slice(&0u32.to_le_bytes()); // max_packet_size
+        body.push(0); // character_set
+        body.extend_from_slice(&[0u8; 23]); // reserved
+        body.extend_from_slice(b"root\0"); // username
+        body.push(0); // auth_response length (no ClientPluginAuthLenencClientData)
+        connection
+            .set_handshake_response(&Packet {
+                header: PacketHeader { size: body.len(), seq: 0 },
+                body,
+                p_type: PacketType::Other,
+            })
+            .unwrap();
+        connection
+    }
+
+    #[test]
+    fn ok_data_rejects_a_session_track_record_with_a_forged_length() {
+        let connection = connection_with_session_track();
+
+        let mut body = vec![0x00]; // status byte
+        body.push(0); // affected_rows (lenenc 0)
+        body.push(0); // last_insert_id (lenenc 0)
+        body.extend_from_slice(&(ServerStatusFlags::ServerSessionStateChanged as u16).to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes()); // warnings
+        body.push(0); // info (lenenc-encoded empty string)
+        body.push(2); // session_state_changes_len (lenenc 2)
+        body.push(0); // record_type: SystemVariableChanged
+        body.push(100); // record_len (lenenc 100) - far more than the 0 bytes that follow
+
+        let packet = Packet {
+            header: PacketHeader { size: body.len(), seq: 0 },
+            body,
+            p_type: PacketType::Ok,
+        };
+
+        let err = OkData::from_packet(&packet, &connection).unwrap_err();
+        assert!(matches!(err, ProtocolError::Malformed));
     }
 }
@@ -0,0 +1,614 @@
+use crate::mysql::error::ProtocolError;
+use crate::mysql::packet::Packet;
+use crate::mysql::resultset::ColumnDef;
+use crate::mysql::types::{Converter, IntFixedLen, StringLenEnc};
+
+/// A `COM_STMT_PREPARE` request: a single command byte (0x16) followed by the
+/// query text running to the end of the packet.
+#[derive(Debug, Clone)]
+pub struct StmtPrepare {
+    pub query: String,
+}
+
+impl StmtPrepare {
+    pub fn from_packet(packet: &Packet) -> StmtPrepare {
+        StmtPrepare {
+            query: String::from_utf8_lossy(&packet.body[1..]).into_owned(),
+        }
+    }
+}
+
+/// The `COM_STMT_PREPARE_OK` response. `param_count` param definition packets
+/// and then `column_count` column definition packets follow this one, each in
+/// the same column-definition
+/// packet format as a `ResultSet`'s (see [`ColumnDef`]).
+#[derive(Debug, Clone)]
+pub struct StmtPrepareOk {
+    pub statement_id: u32,
+    pub column_count: u16,
+    pub param_count: u16,
+    pub warning_count: u16,
+}
+
+impl StmtPrepareOk {
+    pub fn from_packet(packet: &Packet) -> Result<StmtPrepareOk, ProtocolError> {
+        let body = &packet.body;
+        let mut offset = 1; // status byte, always 0x00
+
+        let statement_id = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(4));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+        let column_count = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u16
+        };
+        let param_count = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u16
+        };
+        offset += 1; // filler
+
+        let warning_count = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u16
+        };
+        if offset != body.len() {
+            return Err(ProtocolError::Malformed);
+        }
+
+        Ok(StmtPrepareOk {
+            statement_id,
+            column_count,
+            param_count,
+            warning_count,
+        })
+    }
+}
+
+/// A value carried by the binary protocol, either as a bound `COM_STMT_EXECUTE`
+/// parameter or as a column in a binary result row. Which variant a given
+/// column/param decodes to is driven entirely by its MySQL column-type byte.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryValue {
+    Null,
+    Signed(i64),
+    Unsigned(u64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Date {
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        micro_second: u32,
+    },
+    Time {
+        negative: bool,
+        days: u32,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        micro_second: u32,
+    },
+}
+
+/// MySQL column-type bytes relevant to binary (de)coding. Not every
+/// `MYSQL_TYPE_*` constant needs its own case: anything not fixed-width or
+/// temporal is length-encoded the same way (`VARCHAR`, `VAR_STRING`,
+/// `STRING`, `BLOB`, `DECIMAL`, ...).
+mod column_type {
+    pub const TINY: u8 = 0x01;
+    pub const SHORT: u8 = 0x02;
+    pub const LONG: u8 = 0x03;
+    pub const FLOAT: u8 = 0x04;
+    pub const DOUBLE: u8 = 0x05;
+    pub const TIMESTAMP: u8 = 0x07;
+    pub const LONGLONG: u8 = 0x08;
+    pub const INT24: u8 = 0x09;
+    pub const DATE: u8 = 0x0a;
+    pub const TIME: u8 = 0x0b;
+    pub const DATETIME: u8 = 0x0c;
+    pub const YEAR: u8 = 0x0d;
+}
+
+/// The flag carried in the high byte of a `COM_STMT_EXECUTE` bound parameter's
+/// 2-byte type code, marking the value as unsigned.
+const PARAM_UNSIGNED_FLAG: u16 = 0x8000;
+
+/// `ColumnDef.flags`' bit marking a result column as unsigned.
+const COLUMN_UNSIGNED_FLAG: u16 = 0x0020;
+
+/// Decodes a single binary-protocol value of `column_type` starting at
+/// `offset`, returning the value and the offset of the byte after it. Shared
+/// between `COM_STMT_EXECUTE` bound parameters and binary result rows, since
+/// both encode values the same way. `is_unsigned` comes from the parameter
+/// type's `PARAM_UNSIGNED_FLAG` bit or the column's `COLUMN_UNSIGNED_FLAG`
+/// bit, and only matters for the fixed-width integer types.
+fn decode_value(body: &[u8], offset: usize, column_type: u8, is_unsigned: bool) -> (BinaryValue, usize) {
+    match column_type {
+        column_type::TINY => {
+            let value = body[offset];
+            (
+                if is_unsigned {
+                    BinaryValue::Unsigned(value as u64)
+                } else {
+                    BinaryValue::Signed(value as i8 as i64)
+                },
+                offset + 1,
+            )
+        }
+        column_type::SHORT | column_type::YEAR => {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            (
+                if is_unsigned {
+                    BinaryValue::Unsigned(result.result as u16 as u64)
+                } else {
+                    BinaryValue::Signed(result.result as i16 as i64)
+                },
+                offset + result.offset_increment,
+            )
+        }
+        column_type::LONG | column_type::INT24 => {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(4));
+            (
+                if is_unsigned {
+                    BinaryValue::Unsigned(result.result as u32 as u64)
+                } else {
+                    BinaryValue::Signed(result.result as i32 as i64)
+                },
+                offset + result.offset_increment,
+            )
+        }
+        column_type::LONGLONG => {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(8));
+            (
+                if is_unsigned {
+                    BinaryValue::Unsigned(result.result as u64)
+                } else {
+                    BinaryValue::Signed(result.result as i64)
+                },
+                offset + result.offset_increment,
+            )
+        }
+        column_type::FLOAT => {
+            let bytes: [u8; 4] = body[offset..offset + 4]
+                .try_into()
+                .expect("Slice with incorrect length");
+            (BinaryValue::Float(f32::from_le_bytes(bytes)), offset + 4)
+        }
+        column_type::DOUBLE => {
+            let bytes: [u8; 8] = body[offset..offset + 8]
+                .try_into()
+                .expect("Slice with incorrect length");
+            (BinaryValue::Double(f64::from_le_bytes(bytes)), offset + 8)
+        }
+        column_type::DATE | column_type::DATETIME | column_type::TIMESTAMP => {
+            decode_date(body, offset)
+        }
+        column_type::TIME => decode_time(body, offset),
+        _ => {
+            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            (
+                BinaryValue::String(result.result),
+                offset + result.offset_increment,
+            )
+        }
+    }
+}
+
+/// `DATE`/`DATETIME`/`TIMESTAMP` are encoded as a 1-byte length followed by
+/// that many fields: 0 bytes (zero value), 4 (date only), 7 (+ time), or 11
+/// (+ microseconds).
+fn decode_date(body: &[u8], offset: usize) -> (BinaryValue, usize) {
+    let len = body[offset] as usize;
+    let mut o = offset + 1;
+
+    let mut year = 0u16;
+    let mut month = 0u8;
+    let mut day = 0u8;
+    let mut hour = 0u8;
+    let mut minute = 0u8;
+    let mut second = 0u8;
+    let mut micro_second = 0u32;
+
+    if len >= 4 {
+        let result = IntFixedLen::from_bytes(&body[o..].to_vec(), Some(2));
+        year = result.result as u16;
+        o += result.offset_increment;
+        month = body[o];
+        o += 1;
+        day = body[o];
+        o += 1;
+    }
+    if len >= 7 {
+        hour = body[o];
+        o += 1;
+        minute = body[o];
+        o += 1;
+        second = body[o];
+        o += 1;
+    }
+    if len >= 11 {
+        let result = IntFixedLen::from_bytes(&body[o..].to_vec(), Some(4));
+        micro_second = result.result as u32;
+        o += result.offset_increment;
+    }
+
+    (
+        BinaryValue::Date {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            micro_second,
+        },
+        offset + 1 + len,
+    )
+}
+
+/// `TIME` is encoded as a 1-byte length followed by: 0 bytes (zero value), 8
+/// (sign + days + h/m/s), or 12 (+ microseconds).
+fn decode_time(body: &[u8], offset: usize) -> (BinaryValue, usize) {
+    let len = body[offset] as usize;
+    let mut o = offset + 1;
+
+    let mut negative = false;
+    let mut days = 0u32;
+    let mut hour = 0u8;
+    let mut minute = 0u8;
+    let mut second = 0u8;
+    let mut micro_second = 0u32;
+
+    if len >= 8 {
+        negative = body[o] != 0;
+        o += 1;
+        let result = IntFixedLen::from_bytes(&body[o..].to_vec(), Some(4));
+        days = result.result as u32;
+        o += result.offset_increment;
+        hour = body[o];
+        o += 1;
+        minute = body[o];
+        o += 1;
+        second = body[o];
+        o += 1;
+    }
+    if len >= 12 {
+        let result = IntFixedLen::from_bytes(&body[o..].to_vec(), Some(4));
+        micro_second = result.result as u32;
+        o += result.offset_increment;
+    }
+
+    (
+        BinaryValue::Time {
+            negative,
+            days,
+            hour,
+            minute,
+            second,
+            micro_second,
+        },
+        offset + 1 + len,
+    )
+}
+
+fn is_null(bitmap: &[u8], bit_index: usize) -> bool {
+    (bitmap[bit_index / 8] >> (bit_index % 8)) & 1 != 0
+}
+
+/// A `COM_STMT_EXECUTE` request for a previously prepared statement.
+/// `param_count` (from the matching `StmtPrepareOk`) has to be threaded in
+/// from outside, since the packet itself carries no count.
+#[derive(Debug, Clone)]
+pub struct StmtExecute {
+    pub statement_id: u32,
+    pub flags: u8,
+    pub iteration_count: u32,
+    pub new_params_bound: bool,
+    pub param_types: Vec<u16>,
+    pub params: Vec<BinaryValue>,
+}
+
+impl StmtExecute {
+    pub fn from_packet(packet: &Packet, param_count: usize) -> Result<StmtExecute, ProtocolError> {
+        let body = &packet.body;
+        let mut offset = 1; // command byte 0x17
+
+        let statement_id = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(4));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+        let flags = *body.get(offset).ok_or(ProtocolError::Malformed)?;
+        offset += 1;
+        let iteration_count = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(4));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+
+        let mut new_params_bound = false;
+        let mut param_types = Vec::new();
+        let mut params = Vec::new();
+
+        if param_count > 0 {
+            let bitmap_len = (param_count + 7) / 8;
+            if offset + bitmap_len > body.len() {
+                return Err(ProtocolError::Malformed);
+            }
+            let null_bitmap = body[offset..offset + bitmap_len].to_vec();
+            offset += bitmap_len;
+
+            new_params_bound = *body.get(offset).ok_or(ProtocolError::Malformed)? != 0;
+            offset += 1;
+
+            if new_params_bound {
+                for _ in 0..param_count {
+                    let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+                    offset += result.offset_increment;
+                    param_types.push(result.result as u16);
+                }
+
+                for (i, param_type) in param_types.iter().enumerate() {
+                    if is_null(&null_bitmap, i) {
+                        params.push(BinaryValue::Null);
+                    } else {
+                        let is_unsigned = param_type & PARAM_UNSIGNED_FLAG != 0;
+                        let (value, new_offset) =
+                            decode_value(body, offset, (*param_type & 0xff) as u8, is_unsigned);
+                        offset = new_offset;
+                        params.push(value);
+                    }
+                }
+            }
+        }
+
+        Ok(StmtExecute {
+            statement_id,
+            flags,
+            iteration_count,
+            new_params_bound,
+            param_types,
+            params,
+        })
+    }
+}
+
+/// A single row of a prepared statement's binary result set.
+#[derive(Debug, Clone)]
+pub struct BinaryRow {
+    pub values: Vec<BinaryValue>,
+}
+
+impl BinaryRow {
+    /// The binary row's NULL bitmap is offset by 2 bits relative to a param
+    /// bitmap, to leave room for the packet's leading 0x00 header byte's two
+    /// reserved bits.
+    const NULL_BITMAP_OFFSET: usize = 2;
+
+    pub fn from_packet(packet: &Packet, columns: &[ColumnDef]) -> Result<BinaryRow, ProtocolError> {
+        let body = &packet.body;
+        let mut offset = 1; // packet header byte, always 0x00
+
+        let bitmap_len = (columns.len() + Self::NULL_BITMAP_OFFSET + 7) / 8;
+        if offset + bitmap_len > body.len() {
+            return Err(ProtocolError::Malformed);
+        }
+        let null_bitmap = body[offset..offset + bitmap_len].to_vec();
+        offset += bitmap_len;
+
+        let mut values = Vec::with_capacity(columns.len());
+        for (i, column) in columns.iter().enumerate() {
+            if is_null(&null_bitmap, i + Self::NULL_BITMAP_OFFSET) {
+                values.push(BinaryValue::Null);
+            } else {
+                let is_unsigned = column.flags & COLUMN_UNSIGNED_FLAG != 0;
+                let (value, new_offset) = decode_value(body, offset, column.column_type, is_unsigned);
+                offset = new_offset;
+                values.push(value);
+            }
+        }
+
+        Ok(BinaryRow { values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mysql::packet::PacketHeader;
+
+    fn packet_with_body(body: Vec<u8>) -> Packet {
+        Packet {
+            header: PacketHeader { size: body.len(), seq: 0 },
+            body,
+            p_type: crate::mysql::packet::PacketType::Other,
+        }
+    }
+
+    fn column_def(column_type: u8, flags: u16) -> ColumnDef {
+        ColumnDef {
+            catalog: String::new(),
+            schema: String::new(),
+            table: String::new(),
+            org_table: String::new(),
+            name: String::new(),
+            org_name: String::new(),
+            charset: 0,
+            column_length: 0,
+            column_type,
+            flags,
+            decimals: 0,
+        }
+    }
+
+    #[test]
+    fn decode_value_reads_longlong_as_unsigned_when_flagged() {
+        let body = 0xFFFF_FFFF_FFFF_FFFFu64.to_le_bytes().to_vec();
+        let (value, offset) = decode_value(&body, 0, column_type::LONGLONG, true);
+        assert_eq!(value, BinaryValue::Unsigned(u64::MAX));
+        assert_eq!(offset, 8);
+
+        let (value, _) = decode_value(&body, 0, column_type::LONGLONG, false);
+        assert_eq!(value, BinaryValue::Signed(-1));
+    }
+
+    #[test]
+    fn decode_value_reads_tiny_short_long_as_unsigned_when_flagged() {
+        let tiny = decode_value(&[0xff], 0, column_type::TINY, true);
+        assert_eq!(tiny.0, BinaryValue::Unsigned(255));
+
+        let short_body = 0xffffu16.to_le_bytes().to_vec();
+        let short = decode_value(&short_body, 0, column_type::SHORT, true);
+        assert_eq!(short.0, BinaryValue::Unsigned(0xffff));
+
+        let long_body = 0xffff_ffffu32.to_le_bytes().to_vec();
+        let long = decode_value(&long_body, 0, column_type::LONG, true);
+        assert_eq!(long.0, BinaryValue::Unsigned(0xffff_ffff));
+    }
+
+    #[test]
+    fn decode_date_handles_every_variable_length_encoding() {
+        let (zero, offset) = decode_date(&[0x00], 0);
+        assert_eq!(offset, 1);
+        assert!(matches!(zero, BinaryValue::Date { year: 0, month: 0, day: 0, .. }));
+
+        let mut date_only = vec![4u8];
+        date_only.extend_from_slice(&2024u16.to_le_bytes());
+        date_only.push(3); // month
+        date_only.push(14); // day
+        let (value, offset) = decode_date(&date_only, 0);
+        assert_eq!(offset, 1 + 4);
+        assert!(matches!(
+            value,
+            BinaryValue::Date { year: 2024, month: 3, day: 14, hour: 0, minute: 0, second: 0, micro_second: 0 }
+        ));
+
+        let mut datetime = vec![7u8];
+        datetime.extend_from_slice(&2024u16.to_le_bytes());
+        datetime.extend_from_slice(&[3, 14, 9, 30, 0]);
+        let (value, offset) = decode_date(&datetime, 0);
+        assert_eq!(offset, 1 + 7);
+        assert!(matches!(
+            value,
+            BinaryValue::Date { hour: 9, minute: 30, second: 0, micro_second: 0, .. }
+        ));
+
+        let mut with_micros = vec![11u8];
+        with_micros.extend_from_slice(&2024u16.to_le_bytes());
+        with_micros.extend_from_slice(&[3, 14, 9, 30, 0]);
+        with_micros.extend_from_slice(&500_000u32.to_le_bytes());
+        let (value, offset) = decode_date(&with_micros, 0);
+        assert_eq!(offset, 1 + 11);
+        assert!(matches!(value, BinaryValue::Date { micro_second: 500_000, .. }));
+    }
+
+    #[test]
+    fn decode_time_handles_every_variable_length_encoding() {
+        let (zero, offset) = decode_time(&[0x00], 0);
+        assert_eq!(offset, 1);
+        assert!(matches!(zero, BinaryValue::Time { days: 0, .. }));
+
+        let mut basic = vec![8u8];
+        basic.push(1); // negative
+        basic.extend_from_slice(&2u32.to_le_bytes()); // days
+        basic.extend_from_slice(&[10, 20, 30]); // h/m/s
+        let (value, offset) = decode_time(&basic, 0);
+        assert_eq!(offset, 1 + 8);
+        assert!(matches!(
+            value,
+            BinaryValue::Time { negative: true, days: 2, hour: 10, minute: 20, second: 30, micro_second: 0 }
+        ));
+
+        let mut with_micros = vec![12u8];
+        with_micros.push(0);
+        with_micros.extend_from_slice(&0u32.to_le_bytes());
+        with_micros.extend_from_slice(&[0, 0, 0]);
+        with_micros.extend_from_slice(&250_000u32.to_le_bytes());
+        let (value, offset) = decode_time(&with_micros, 0);
+        assert_eq!(offset, 1 + 12);
+        assert!(matches!(value, BinaryValue::Time { micro_second: 250_000, .. }));
+    }
+
+    fn stmt_execute_body(param_type: u16, null: bool, value_bytes: &[u8]) -> Vec<u8> {
+        let mut body = vec![0x17]; // command byte
+        body.extend_from_slice(&7u32.to_le_bytes()); // statement_id
+        body.push(0); // flags
+        body.extend_from_slice(&1u32.to_le_bytes()); // iteration_count
+        body.push(if null { 0x01 } else { 0x00 }); // null_bitmap, 1 param
+        body.push(1); // new_params_bound
+        body.extend_from_slice(&param_type.to_le_bytes());
+        if !null {
+            body.extend_from_slice(value_bytes);
+        }
+        body
+    }
+
+    #[test]
+    fn stmt_execute_preserves_the_unsigned_flag_from_the_param_type() {
+        let value_bytes = 0xffff_ffff_ffff_ffffu64.to_le_bytes();
+        let param_type = column_type::LONGLONG as u16 | PARAM_UNSIGNED_FLAG;
+        let packet = packet_with_body(stmt_execute_body(param_type, false, &value_bytes));
+
+        let stmt_execute = StmtExecute::from_packet(&packet, 1).unwrap();
+        assert!(stmt_execute.new_params_bound);
+        assert_eq!(stmt_execute.params, vec![BinaryValue::Unsigned(u64::MAX)]);
+    }
+
+    #[test]
+    fn stmt_execute_honors_the_null_bitmap_for_bound_params() {
+        let packet = packet_with_body(stmt_execute_body(column_type::LONGLONG as u16, true, &[]));
+        let stmt_execute = StmtExecute::from_packet(&packet, 1).unwrap();
+        assert_eq!(stmt_execute.params, vec![BinaryValue::Null]);
+    }
+
+    #[test]
+    fn stmt_execute_skips_param_types_when_new_params_bound_is_false() {
+        let mut body = vec![0x17];
+        body.extend_from_slice(&7u32.to_le_bytes());
+        body.push(0);
+        body.extend_from_slice(&1u32.to_le_bytes());
+        body.push(0); // null_bitmap, 1 param
+        body.push(0); // new_params_bound = false
+        let packet = packet_with_body(body);
+
+        let stmt_execute = StmtExecute::from_packet(&packet, 1).unwrap();
+        assert!(!stmt_execute.new_params_bound);
+        assert!(stmt_execute.param_types.is_empty());
+        assert!(stmt_execute.params.is_empty());
+    }
+
+    #[test]
+    fn binary_row_null_bitmap_is_offset_by_two_bits() {
+        // With NULL_BITMAP_OFFSET == 2, a single column's NULL bit lives at
+        // bit index 2 of the bitmap, not bit index 0.
+        let columns = vec![column_def(column_type::LONGLONG, 0)];
+        let mut body = vec![0x00]; // packet header byte
+        body.push(0b0000_0100); // bit 2 set -> column 0 is NULL
+        let packet = packet_with_body(body);
+
+        let row = BinaryRow::from_packet(&packet, &columns).unwrap();
+        assert_eq!(row.values, vec![BinaryValue::Null]);
+    }
+
+    #[test]
+    fn binary_row_reads_an_unsigned_column_from_flags() {
+        let columns = vec![column_def(column_type::LONGLONG, COLUMN_UNSIGNED_FLAG)];
+        let mut body = vec![0x00]; // packet header byte
+        body.push(0b0000_0000); // no NULLs
+        body.extend_from_slice(&0xffff_ffff_ffff_ffffu64.to_le_bytes());
+        let packet = packet_with_body(body);
+
+        let row = BinaryRow::from_packet(&packet, &columns).unwrap();
+        assert_eq!(row.values, vec![BinaryValue::Unsigned(u64::MAX)]);
+    }
+}
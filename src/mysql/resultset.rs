@@ -0,0 +1,377 @@
+use crate::connection::Connection;
+use crate::mysql::accumulator::CapabilityFlags;
+use crate::mysql::error::ProtocolError;
+use crate::mysql::packet::{ErrorData, Packet, PacketType};
+use crate::mysql::types::{Converter, IntFixedLen, IntLenEnc, StringLenEnc};
+
+/// A single column's metadata from a column-definition packet.
+#[derive(Debug, Clone)]
+pub struct ColumnDef {
+    pub catalog: String,
+    pub schema: String,
+    pub table: String,
+    pub org_table: String,
+    pub name: String,
+    pub org_name: String,
+    pub charset: u16,
+    pub column_length: u32,
+    pub column_type: u8,
+    pub flags: u16,
+    pub decimals: u8,
+}
+
+impl ColumnDef {
+    pub fn from_packet(packet: &Packet) -> Result<ColumnDef, ProtocolError> {
+        let body = &packet.body;
+        let mut offset = 0;
+
+        let catalog = {
+            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+        let schema = {
+            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+        let table = {
+            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+        let org_table = {
+            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+        let name = {
+            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+        let org_name = {
+            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+
+        // Length-encoded filler preceding the fixed fields below; its value is
+        // always 0x0c and carries no information.
+        let _filler = {
+            let result = IntLenEnc::from_bytes(&body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            result.result
+        };
+
+        let charset = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u16
+        };
+        let column_length = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(4));
+            offset += result.offset_increment;
+            result.result as u32
+        };
+        let column_type = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(1));
+            offset += result.offset_increment;
+            result.result as u8
+        };
+        let flags = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+            offset += result.offset_increment;
+            result.result as u16
+        };
+        let decimals = {
+            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(1));
+            offset += result.offset_increment;
+            result.result as u8
+        };
+        if offset > body.len() {
+            return Err(ProtocolError::Malformed);
+        }
+
+        Ok(ColumnDef {
+            catalog,
+            schema,
+            table,
+            org_table,
+            name,
+            org_name,
+            charset,
+            column_length,
+            column_type,
+            flags,
+            decimals,
+        })
+    }
+}
+
+/// A single column value from a text-protocol row packet.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Text(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub values: Vec<Value>,
+}
+
+impl Row {
+    pub fn from_packet(packet: &Packet, column_count: usize) -> Result<Row, ProtocolError> {
+        let body = &packet.body;
+        let mut offset = 0;
+        let mut values = Vec::with_capacity(column_count);
+
+        for _ in 0..column_count {
+            if offset >= body.len() {
+                return Err(ProtocolError::Malformed);
+            }
+            if body[offset] == 0xfb {
+                values.push(Value::Null);
+                offset += 1;
+            } else {
+                let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+                offset += result.offset_increment;
+                values.push(Value::Text(result.result));
+            }
+        }
+
+        Ok(Row { values })
+    }
+}
+
+#[derive(Debug)]
+pub struct ResultSet {
+    pub columns: Vec<ColumnDef>,
+    pub rows: Vec<Row>,
+}
+
+/// Where a `ResultSetBuilder` is in a `COM_QUERY` response: column count, then
+/// that many column definitions, then (pre-`CLIENT_DEPRECATE_EOF`) an EOF
+/// packet closing the column block, then rows until the terminating EOF/OK.
+#[derive(Debug)]
+enum ResultSetState {
+    AwaitingColumnCount,
+    AwaitingColumnDefs { remaining: usize },
+    AwaitingColumnsEof,
+    AwaitingRows,
+    Done,
+}
+
+/// Incrementally assembles a `ResultSet` out of the packet sequence following a
+/// non-OK/non-Error `COM_QUERY` response, one `Packet` at a time, so
+/// `Connection` can drive it directly off the wire without buffering the whole
+/// response itself.
+#[derive(Debug)]
+pub struct ResultSetBuilder {
+    state: ResultSetState,
+    columns: Vec<ColumnDef>,
+    rows: Vec<Row>,
+    /// Whether `CLIENT_DEPRECATE_EOF` was negotiated, in which case the
+    /// server omits the EOF packet that would otherwise close the column
+    /// block - the packet right after the last column definition is already
+    /// the first row (or the terminating EOF/OK of a zero-row result).
+    deprecate_eof: bool,
+}
+
+impl ResultSetBuilder {
+    /// `connection`'s negotiated `HandshakeResponse41.client_flag` decides
+    /// whether the column block is closed by an EOF packet, the same way
+    /// `OkData::from_packet` reads capability flags off it.
+    pub fn new(connection: &Connection) -> ResultSetBuilder {
+        let deprecate_eof = connection.get_handshake_response().unwrap().client_flag
+            & CapabilityFlags::ClientDeprecateEof as u32
+            != 0;
+
+        ResultSetBuilder {
+            state: ResultSetState::AwaitingColumnCount,
+            columns: Vec::new(),
+            rows: Vec::new(),
+            deprecate_eof,
+        }
+    }
+
+    /// Feeds the next packet of the response in. Returns the finished
+    /// `ResultSet` once the row block's terminating EOF/OK packet arrives, and
+    /// `None` for every packet before that.
+    ///
+    /// The server can abort a result set with an Error packet in place of a
+    /// row at any point; that's parsed into an `ErrorData` and reported as
+    /// `ProtocolError::ServerError` rather than fed to `Row::from_packet`.
+    /// `connection` is only needed for this case, to resolve `ErrorData`'s
+    /// capability-flag-dependent SQL state field, the same way
+    /// `ResultSetBuilder::new` uses it to resolve `CLIENT_DEPRECATE_EOF`.
+    pub fn feed(&mut self, packet: &Packet, connection: &Connection) -> Result<Option<ResultSet>, ProtocolError> {
+        match self.state {
+            ResultSetState::AwaitingColumnCount => {
+                let result = IntLenEnc::from_bytes(&packet.body, None);
+                let column_count = result.result as usize;
+                self.state = if column_count == 0 {
+                    ResultSetState::AwaitingRows
+                } else {
+                    ResultSetState::AwaitingColumnDefs {
+                        remaining: column_count,
+                    }
+                };
+                Ok(None)
+            }
+            ResultSetState::AwaitingColumnDefs { remaining } => {
+                self.columns.push(ColumnDef::from_packet(packet)?);
+                self.state = if remaining > 1 {
+                    ResultSetState::AwaitingColumnDefs {
+                        remaining: remaining - 1,
+                    }
+                } else if self.deprecate_eof {
+                    ResultSetState::AwaitingRows
+                } else {
+                    ResultSetState::AwaitingColumnsEof
+                };
+                Ok(None)
+            }
+            ResultSetState::AwaitingColumnsEof => {
+                self.state = ResultSetState::AwaitingRows;
+                Ok(None)
+            }
+            ResultSetState::AwaitingRows => {
+                if packet.p_type == PacketType::Eof || packet.p_type == PacketType::Ok {
+                    self.state = ResultSetState::Done;
+                    return Ok(Some(ResultSet {
+                        columns: std::mem::take(&mut self.columns),
+                        rows: std::mem::take(&mut self.rows),
+                    }));
+                }
+                if packet.p_type == PacketType::Error {
+                    self.state = ResultSetState::Done;
+                    return Err(ProtocolError::ServerError(ErrorData::from_packet(packet, connection)?));
+                }
+                self.rows.push(Row::from_packet(packet, self.columns.len())?);
+                Ok(None)
+            }
+            ResultSetState::Done => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mysql::accumulator::CapabilityFlags;
+    use crate::mysql::packet::PacketHeader;
+
+    fn lenenc_int(n: u64) -> Vec<u8> {
+        assert!(n < 0xfb, "test helper only covers single-byte lenenc ints");
+        vec![n as u8]
+    }
+
+    fn lenenc_str(s: &str) -> Vec<u8> {
+        let mut bytes = lenenc_int(s.len() as u64);
+        bytes.extend_from_slice(s.as_bytes());
+        bytes
+    }
+
+    fn packet_with_body(body: Vec<u8>, p_type: PacketType) -> Packet {
+        Packet {
+            header: PacketHeader { size: body.len(), seq: 0 },
+            body,
+            p_type,
+        }
+    }
+
+    fn column_def_body(name: &str) -> Vec<u8> {
+        let mut body = lenenc_str("def"); // catalog
+        body.extend(lenenc_str("test_schema"));
+        body.extend(lenenc_str("test_table")); // table
+        body.extend(lenenc_str("test_table")); // org_table
+        body.extend(lenenc_str(name));
+        body.extend(lenenc_str(name)); // org_name
+        body.extend(lenenc_int(0x0c)); // filler
+        body.extend_from_slice(&0x21u16.to_le_bytes()); // charset
+        body.extend_from_slice(&10u32.to_le_bytes()); // column_length
+        body.push(0xfd); // column_type: VAR_STRING
+        body.extend_from_slice(&0u16.to_le_bytes()); // flags
+        body.push(0); // decimals
+        body
+    }
+
+    fn connection_with_deprecate_eof() -> Connection {
+        let mut connection = Connection::new();
+        let mut body = (CapabilityFlags::ClientDeprecateEof as u32).to_le_bytes().to_vec();
+        body.extend_from_slice(&0u32.to_le_bytes()); // max_packet_size
+        body.push(0); // character_set
+        body.extend_from_slice(&[0u8; 23]); // reserved
+        body.extend_from_slice(b"root\0"); // username
+        body.push(0); // auth_response length (no ClientPluginAuthLenencClientData)
+        connection.set_handshake_response(&packet_with_body(body, PacketType::Other)).unwrap();
+        connection
+    }
+
+    #[test]
+    fn skips_column_eof_when_deprecate_eof_is_negotiated() {
+        let connection = connection_with_deprecate_eof();
+        let mut builder = ResultSetBuilder::new(&connection);
+
+        assert!(builder
+            .feed(&packet_with_body(lenenc_int(1), PacketType::Other), &connection)
+            .unwrap()
+            .is_none());
+        assert!(builder
+            .feed(&packet_with_body(column_def_body("col1"), PacketType::Other), &connection)
+            .unwrap()
+            .is_none());
+
+        // With CLIENT_DEPRECATE_EOF there is no EOF packet closing the column
+        // block - this packet is already the first row, and it must be
+        // decoded as one rather than silently dropped.
+        let row_body = lenenc_str("hello");
+        assert!(builder
+            .feed(&packet_with_body(row_body, PacketType::Other), &connection)
+            .unwrap()
+            .is_none());
+
+        let result_set = builder
+            .feed(&packet_with_body(Vec::new(), PacketType::Ok), &connection)
+            .unwrap()
+            .expect("terminating OK packet should complete the result set");
+        assert_eq!(result_set.rows.len(), 1);
+    }
+
+    #[test]
+    fn mid_result_set_error_packet_is_reported_as_server_error() {
+        let connection = connection_with_deprecate_eof();
+        let mut builder = ResultSetBuilder::new(&connection);
+
+        assert!(builder
+            .feed(&packet_with_body(lenenc_int(0), PacketType::Other), &connection)
+            .unwrap()
+            .is_none());
+
+        let mut error_body = vec![0xff];
+        error_body.extend_from_slice(&1064u16.to_le_bytes()); // error_code
+        error_body.extend_from_slice(b"#42000"); // sql state marker + state
+        error_body.extend_from_slice(b"syntax error");
+
+        let err = builder
+            .feed(&packet_with_body(error_body, PacketType::Error), &connection)
+            .unwrap_err();
+        match err {
+            ProtocolError::ServerError(error) => {
+                assert_eq!(error.error_code, 1064);
+                assert_eq!(error.error_message, "syntax error");
+            }
+            other => panic!("expected ServerError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn row_from_packet_rejects_a_body_truncated_before_the_last_column() {
+        // column_count claims 2 values but the body only has bytes for 1.
+        let body = lenenc_str("only_one");
+        let err = Row::from_packet(&packet_with_body(body, PacketType::Other), 2).unwrap_err();
+        assert!(matches!(err, ProtocolError::Malformed));
+    }
+}